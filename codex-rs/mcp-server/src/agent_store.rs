@@ -0,0 +1,194 @@
+//! Persistence layer for subagent lifecycle state.
+//!
+//! `AsyncSubAgentIntegration` keeps agent states, thinking summaries, and
+//! token accounting in memory, so restarting the stdio server loses every
+//! in-flight and completed agent. [`AgentStore`] is an optional backend
+//! that mirrors that state durably; [`PostgresAgentStore`] is the bundled
+//! implementation. Users who don't configure a store keep today's
+//! in-memory-only behavior.
+
+use std::sync::Arc;
+
+use bb8::Pool;
+use bb8_postgres::PostgresConnectionManager;
+use codex_core::async_subagent_integration::AgentType;
+use tokio_postgres::NoTls;
+use tracing::info;
+
+/// A durably-recorded snapshot of an agent at the moment it was persisted,
+/// used to reload outstanding work after an MCP restart.
+#[derive(Debug, Clone)]
+pub struct PersistedAgentState {
+    pub agent_id: String,
+    pub agent_type: AgentType,
+    pub task: String,
+    pub status: String,
+    pub progress: f32,
+}
+
+/// Backend for recording subagent lifecycle events so they survive an MCP
+/// restart. Implementations must tolerate being called from many agents
+/// concurrently.
+#[async_trait::async_trait]
+pub trait AgentStore: Send + Sync {
+    /// Record that an agent has started.
+    async fn record_start(&self, agent_id: &str, agent_type: AgentType, task: &str) -> anyhow::Result<()>;
+
+    /// Record a status/progress transition for an agent.
+    async fn record_transition(&self, agent_id: &str, status: &str, progress: f32) -> anyhow::Result<()>;
+
+    /// Append to an agent's thinking log.
+    async fn record_thinking(&self, agent_id: &str, thinking: &str) -> anyhow::Result<()>;
+
+    /// Record token usage for an agent.
+    async fn record_tokens(&self, agent_id: &str, tokens_used: u64) -> anyhow::Result<()>;
+
+    /// Load every agent that had not reached a terminal status as of the
+    /// last time it was persisted, so `check_inbox`/`get_status` reflect
+    /// pre-restart work.
+    async fn load_outstanding(&self) -> anyhow::Result<Vec<PersistedAgentState>>;
+}
+
+/// Postgres-backed [`AgentStore`] using a pooled connection manager so the
+/// pool is created once in `run_main` and shared across every agent.
+pub struct PostgresAgentStore {
+    pool: Pool<PostgresConnectionManager<NoTls>>,
+}
+
+impl PostgresAgentStore {
+    /// Connect and create the pool. `run_main` constructs this once (via
+    /// [`configured_store`]) and hands the resulting `Arc<dyn AgentStore>`
+    /// to the subagent tool handler; `AsyncSubAgentIntegration` itself does
+    /// not hold the store.
+    pub async fn connect(database_url: &str) -> anyhow::Result<Self> {
+        let manager = PostgresConnectionManager::new_from_stringlike(database_url, NoTls)?;
+        let pool = Pool::builder().build(manager).await?;
+
+        let conn = pool.get().await?;
+        conn.batch_execute(
+            "CREATE TABLE IF NOT EXISTS subagent_state (
+                agent_id    TEXT PRIMARY KEY,
+                agent_type  TEXT NOT NULL,
+                task        TEXT NOT NULL,
+                status      TEXT NOT NULL,
+                progress    REAL NOT NULL DEFAULT 0,
+                thinking    TEXT NOT NULL DEFAULT '',
+                tokens_used BIGINT NOT NULL DEFAULT 0
+            )",
+        )
+        .await?;
+
+        info!("connected subagent state store to Postgres");
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait::async_trait]
+impl AgentStore for PostgresAgentStore {
+    async fn record_start(&self, agent_id: &str, agent_type: AgentType, task: &str) -> anyhow::Result<()> {
+        let conn = self.pool.get().await?;
+        conn.execute(
+            "INSERT INTO subagent_state (agent_id, agent_type, task, status)
+             VALUES ($1, $2, $3, 'started')
+             ON CONFLICT (agent_id) DO UPDATE SET agent_type = $2, task = $3, status = 'started'",
+            &[&agent_id, &agent_type.as_str(), &task],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn record_transition(&self, agent_id: &str, status: &str, progress: f32) -> anyhow::Result<()> {
+        let conn = self.pool.get().await?;
+        conn.execute(
+            "UPDATE subagent_state SET status = $2, progress = $3 WHERE agent_id = $1",
+            &[&agent_id, &status, &progress],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn record_thinking(&self, agent_id: &str, thinking: &str) -> anyhow::Result<()> {
+        let conn = self.pool.get().await?;
+        conn.execute(
+            "UPDATE subagent_state SET thinking = thinking || $2 WHERE agent_id = $1",
+            &[&agent_id, &thinking],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn record_tokens(&self, agent_id: &str, tokens_used: u64) -> anyhow::Result<()> {
+        let conn = self.pool.get().await?;
+        conn.execute(
+            "UPDATE subagent_state SET tokens_used = $2 WHERE agent_id = $1",
+            &[&agent_id, &(tokens_used as i64)],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn load_outstanding(&self) -> anyhow::Result<Vec<PersistedAgentState>> {
+        let conn = self.pool.get().await?;
+        let rows = conn
+            .query(
+                "SELECT agent_id, agent_type, task, status, progress FROM subagent_state
+                 WHERE status NOT IN ('completed', 'failed')",
+                &[],
+            )
+            .await?;
+
+        let mut states = Vec::with_capacity(rows.len());
+        for row in rows {
+            let agent_type_str: String = row.get("agent_type");
+            let Ok(agent_type) = parse_agent_type(&agent_type_str) else {
+                continue;
+            };
+            states.push(PersistedAgentState {
+                agent_id: row.get("agent_id"),
+                agent_type,
+                task: row.get("task"),
+                status: row.get("status"),
+                progress: row.get("progress"),
+            });
+        }
+        Ok(states)
+    }
+}
+
+/// Every `AgentType` variant, used to invert `AgentType::as_str()` below.
+/// Keeping this list (rather than a hand-written string match) is what
+/// guarantees `parse_agent_type` round-trips whatever `record_start`
+/// actually wrote via `agent_type.as_str()`, instead of drifting into its
+/// own independently-guessed naming convention.
+const ALL_AGENT_TYPES: [AgentType; 8] = [
+    AgentType::CodeExpert,
+    AgentType::SecurityExpert,
+    AgentType::TestingExpert,
+    AgentType::DocsExpert,
+    AgentType::DeepResearcher,
+    AgentType::DebugExpert,
+    AgentType::PerformanceExpert,
+    AgentType::General,
+];
+
+/// Exposed for the round-trip test in `tests/suite/parallel_subagent.rs`,
+/// which checks that this inverts `AgentType::as_str()` for every variant
+/// `record_start` can actually write.
+#[doc(hidden)]
+pub fn parse_agent_type(type_str: &str) -> anyhow::Result<AgentType> {
+    ALL_AGENT_TYPES
+        .iter()
+        .copied()
+        .find(|agent_type| agent_type.as_str() == type_str)
+        .ok_or_else(|| anyhow::anyhow!("unknown persisted agent type: {type_str}"))
+}
+
+/// Construct the configured [`AgentStore`], or `None` if the user has not
+/// opted into persistence (`CODEX_MCP_SUBAGENT_STORE_DATABASE_URL` unset),
+/// in which case agent state stays in-memory-only exactly as before.
+pub async fn configured_store(database_url: Option<&str>) -> anyhow::Result<Option<Arc<dyn AgentStore>>> {
+    match database_url {
+        Some(url) => Ok(Some(Arc::new(PostgresAgentStore::connect(url).await?) as Arc<dyn AgentStore>)),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,97 @@
+//! In-memory error collection for background subagents.
+//!
+//! Today a failing subagent's error is logged and returned once, then lost
+//! – nothing a later `check_inbox` or report can surface. [`ErrChan`] gives
+//! failing code a place to send errors (tagged by agent id) instead of just
+//! bubbling them up, and [`spawn_error_consumer`] drains those errors into
+//! the in-memory [`ErrorLog`] that `get_errors`/`check_inbox` read from.
+//! This does not survive an MCP restart; there's no durable sink wired in
+//! yet (unlike chunk1-3's `AgentStore`, nothing here records *why* an agent
+//! failed, only that it started/transitioned/etc.), so for now errors are
+//! best-effort, in-process only.
+
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+use tokio::sync::mpsc;
+use tracing::error;
+
+/// A single agent failure, tagged so it can be attributed in token/status
+/// reports.
+#[derive(Debug, Clone)]
+pub struct AgentError {
+    pub agent_id: String,
+    pub message: String,
+}
+
+/// Sending half of the error channel. Cloned into whatever code paths can
+/// fail on behalf of a subagent (`handle_subagent_tool_call`,
+/// `AsyncSubAgentIntegration::start_agent`, etc.).
+#[derive(Clone)]
+pub struct ErrChan {
+    tx: mpsc::UnboundedSender<AgentError>,
+}
+
+impl ErrChan {
+    /// Report an agent failure instead of only logging and returning it.
+    /// Never blocks; if the consumer has been dropped the error is logged
+    /// and otherwise discarded rather than panicking the caller.
+    pub fn send(&self, agent_id: impl Into<String>, err: impl std::fmt::Display) {
+        let agent_error = AgentError {
+            agent_id: agent_id.into(),
+            message: err.to_string(),
+        };
+        if self.tx.send(agent_error).is_err() {
+            error!("error consumer task is gone; dropping error report");
+        }
+    }
+}
+
+/// In-memory log of agent errors, filled in by [`spawn_error_consumer`].
+pub struct ErrorLog {
+    errors: Mutex<Vec<AgentError>>,
+}
+
+impl ErrorLog {
+    fn new() -> Self {
+        Self {
+            errors: Mutex::new(Vec::new()),
+        }
+    }
+
+    async fn record(&self, err: AgentError) {
+        self.errors.lock().await.push(err);
+    }
+
+    /// All errors collected so far, most recent last.
+    pub async fn errors(&self) -> Vec<AgentError> {
+        self.errors.lock().await.clone()
+    }
+
+    /// Number of errors collected so far, for a one-line `check_inbox` summary.
+    pub async fn count(&self) -> usize {
+        self.errors.lock().await.len()
+    }
+}
+
+/// Construct a fresh error channel: an [`ErrChan`] for failing code to send
+/// on, and the shared [`ErrorLog`] that [`spawn_error_consumer`] fills in.
+pub fn error_channel() -> (ErrChan, Arc<ErrorLog>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let log = Arc::new(ErrorLog::new());
+    spawn_error_consumer(rx, Arc::clone(&log));
+    (ErrChan { tx }, log)
+}
+
+/// Drain errors from the channel, recording each into `log`.
+pub fn spawn_error_consumer(
+    mut rx: mpsc::UnboundedReceiver<AgentError>,
+    log: Arc<ErrorLog>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(err) = rx.recv().await {
+            log.record(err).await;
+        }
+        error!("error channel sender dropped; consumer exiting");
+    })
+}
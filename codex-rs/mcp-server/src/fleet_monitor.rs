@@ -0,0 +1,273 @@
+//! Live monitoring and metrics for the subagent fleet.
+//!
+//! `AsyncSubAgentIntegration` already tracks per-agent status, progress, and
+//! token usage; this module runs alongside the processor task in
+//! `run_main`, periodically snapshotting that state into a push stream
+//! ([`FleetMonitor::subscribe`]) and a scrapeable Prometheus-style text
+//! endpoint ([`FleetMonitor::render_metrics`]), so operators have a
+//! real-time view of long-running background agents without polling
+//! `check_inbox`/`get_token_report` by hand. It also flags agents whose
+//! progress hasn't moved past [`STALL_THRESHOLD`], and logs a warning when
+//! the fleet's aggregate token spend exceeds the runtime budget; individual
+//! agents' spend is not checked against a budget.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::time::Duration;
+use std::time::Instant;
+
+use codex_core::async_subagent_integration::AgentType;
+use codex_core::async_subagent_integration::AsyncSubAgentIntegration;
+use tokio::sync::Mutex;
+use tokio::sync::broadcast;
+use tracing::warn;
+
+/// How long an agent's progress may sit unchanged before it's flagged as
+/// stalled in a snapshot.
+const STALL_THRESHOLD: Duration = Duration::from_secs(120);
+
+/// Capacity of the broadcast channel snapshots are pushed on; slow
+/// subscribers simply miss the oldest snapshots rather than backing up the
+/// monitor loop.
+const SNAPSHOT_CHANNEL_CAPACITY: usize = 32;
+
+/// Counters for agents started/completed/failed, broken out by
+/// [`AgentType`] so operators can see which kind of agent is misbehaving.
+#[derive(Debug, Clone, Default)]
+pub struct AgentTypeCounters {
+    pub started: u64,
+    pub completed: u64,
+    pub failed: u64,
+}
+
+/// A point-in-time view of the fleet, pushed to subscribers and rendered by
+/// [`FleetMonitor::render_metrics`].
+#[derive(Debug, Clone)]
+pub struct FleetSnapshot {
+    pub active_agents: u64,
+    pub aggregate_tokens_used: u64,
+    pub runtime_budget: usize,
+    pub stalled_agent_ids: Vec<String>,
+    pub counters_by_type: HashMap<String, AgentTypeCounters>,
+}
+
+struct ProgressHistory {
+    last_progress: f32,
+    last_changed_at: Instant,
+}
+
+/// Runs the periodic snapshot loop and owns the cumulative counters.
+pub struct FleetMonitor {
+    runtime_budget: usize,
+    tx: broadcast::Sender<FleetSnapshot>,
+    counters: Mutex<HashMap<String, AgentTypeCounters>>,
+    progress_history: Mutex<HashMap<String, ProgressHistory>>,
+    /// Agent ids already counted into `completed`/`failed`, so a repeated
+    /// snapshot (the agent still showing up before `AsyncSubAgentIntegration`
+    /// forgets about it) doesn't double-count the same terminal state.
+    counted_terminal: Mutex<HashSet<String>>,
+    latest: Mutex<Option<FleetSnapshot>>,
+}
+
+impl FleetMonitor {
+    pub fn new(runtime_budget: usize) -> Self {
+        let (tx, _rx) = broadcast::channel(SNAPSHOT_CHANNEL_CAPACITY);
+        Self {
+            runtime_budget,
+            tx,
+            counters: Mutex::new(HashMap::new()),
+            progress_history: Mutex::new(HashMap::new()),
+            counted_terminal: Mutex::new(HashSet::new()),
+            latest: Mutex::new(None),
+        }
+    }
+
+    /// Subscribe to the push stream of fleet snapshots.
+    pub fn subscribe(&self) -> broadcast::Receiver<FleetSnapshot> {
+        self.tx.subscribe()
+    }
+
+    /// Record that an agent of `agent_type` started, completed, or failed,
+    /// for the cumulative by-type counters.
+    pub async fn record_started(&self, agent_type: AgentType) {
+        self.counters
+            .lock()
+            .await
+            .entry(agent_type.as_str().to_string())
+            .or_default()
+            .started += 1;
+    }
+
+    pub async fn record_completed(&self, agent_type: AgentType) {
+        self.counters
+            .lock()
+            .await
+            .entry(agent_type.as_str().to_string())
+            .or_default()
+            .completed += 1;
+    }
+
+    pub async fn record_failed(&self, agent_type: AgentType) {
+        self.counters
+            .lock()
+            .await
+            .entry(agent_type.as_str().to_string())
+            .or_default()
+            .failed += 1;
+    }
+
+    /// Take one snapshot of the fleet and push it to subscribers. Returns
+    /// the snapshot so callers (tests, the `get_metrics` action) can inspect
+    /// it without a subscription.
+    pub async fn snapshot_once(&self, integration: &AsyncSubAgentIntegration) -> FleetSnapshot {
+        let states = integration.get_agent_states().await;
+
+        let mut stalled_agent_ids = Vec::new();
+        let mut progress_history = self.progress_history.lock().await;
+        let now = Instant::now();
+        for state in &states {
+            let entry = progress_history
+                .entry(state.agent_id.clone())
+                .or_insert(ProgressHistory {
+                    last_progress: state.progress,
+                    last_changed_at: now,
+                });
+            if (state.progress - entry.last_progress).abs() > f32::EPSILON {
+                entry.last_progress = state.progress;
+                entry.last_changed_at = now;
+            } else if now.duration_since(entry.last_changed_at) > STALL_THRESHOLD {
+                warn!(
+                    "agent {} has made no progress for over {:?}; may be stalled",
+                    state.agent_id, STALL_THRESHOLD
+                );
+                stalled_agent_ids.push(state.agent_id.clone());
+            }
+        }
+        // Agents no longer reported by the integration are done; drop their
+        // history so the map doesn't grow unbounded over a long session.
+        let active_ids: std::collections::HashSet<&String> =
+            states.iter().map(|s| &s.agent_id).collect();
+        progress_history.retain(|agent_id, _| active_ids.contains(agent_id));
+        drop(progress_history);
+
+        // Count each agent's terminal state exactly once, the first
+        // snapshot that observes it, so the cumulative completed/failed
+        // counters actually move instead of staying at zero forever.
+        let mut counted_terminal = self.counted_terminal.lock().await;
+        for state in &states {
+            if counted_terminal.contains(&state.agent_id) {
+                continue;
+            }
+            let counted = match state.status.as_str() {
+                "completed" => {
+                    self.record_completed(state.agent_type).await;
+                    true
+                }
+                "failed" => {
+                    self.record_failed(state.agent_type).await;
+                    true
+                }
+                _ => false,
+            };
+            if counted {
+                counted_terminal.insert(state.agent_id.clone());
+            }
+        }
+        counted_terminal.retain(|agent_id| active_ids.contains(agent_id));
+        drop(counted_terminal);
+
+        let aggregate_tokens_used = integration.total_tokens_used().await;
+
+        let snapshot = FleetSnapshot {
+            active_agents: states
+                .iter()
+                .filter(|s| !matches!(s.status.as_str(), "completed" | "failed"))
+                .count() as u64,
+            aggregate_tokens_used,
+            runtime_budget: self.runtime_budget,
+            stalled_agent_ids,
+            counters_by_type: self.counters.lock().await.clone(),
+        };
+
+        if aggregate_tokens_used as usize > self.runtime_budget {
+            warn!(
+                "fleet aggregate token spend ({}) exceeds runtime budget ({})",
+                aggregate_tokens_used, self.runtime_budget
+            );
+        }
+
+        *self.latest.lock().await = Some(snapshot.clone());
+        // A push with no subscribers is not an error; it just means nobody
+        // is watching the live stream right now.
+        let _ = self.tx.send(snapshot.clone());
+        snapshot
+    }
+
+    /// Render the most recent snapshot in Prometheus text exposition
+    /// format, for a scrapeable metrics endpoint.
+    pub async fn render_metrics(&self) -> String {
+        let Some(snapshot) = self.latest.lock().await.clone() else {
+            return String::from("# no fleet snapshot has been taken yet\n");
+        };
+
+        let mut out = String::new();
+        out.push_str("# HELP codex_subagent_active_agents Agents currently running.\n");
+        out.push_str("# TYPE codex_subagent_active_agents gauge\n");
+        out.push_str(&format!(
+            "codex_subagent_active_agents {}\n",
+            snapshot.active_agents
+        ));
+
+        out.push_str("# HELP codex_subagent_tokens_used Aggregate tokens spent by the fleet.\n");
+        out.push_str("# TYPE codex_subagent_tokens_used gauge\n");
+        out.push_str(&format!(
+            "codex_subagent_tokens_used {}\n",
+            snapshot.aggregate_tokens_used
+        ));
+
+        out.push_str("# HELP codex_subagent_stalled_agents Agents with no progress past the stall threshold.\n");
+        out.push_str("# TYPE codex_subagent_stalled_agents gauge\n");
+        out.push_str(&format!(
+            "codex_subagent_stalled_agents {}\n",
+            snapshot.stalled_agent_ids.len()
+        ));
+
+        out.push_str("# HELP codex_subagent_total Agents started/completed/failed by type.\n");
+        out.push_str("# TYPE codex_subagent_total counter\n");
+        let mut agent_types: Vec<&String> = snapshot.counters_by_type.keys().collect();
+        agent_types.sort();
+        for agent_type in agent_types {
+            let counters = &snapshot.counters_by_type[agent_type];
+            out.push_str(&format!(
+                "codex_subagent_total{{agent_type=\"{agent_type}\",outcome=\"started\"}} {}\n",
+                counters.started
+            ));
+            out.push_str(&format!(
+                "codex_subagent_total{{agent_type=\"{agent_type}\",outcome=\"completed\"}} {}\n",
+                counters.completed
+            ));
+            out.push_str(&format!(
+                "codex_subagent_total{{agent_type=\"{agent_type}\",outcome=\"failed\"}} {}\n",
+                counters.failed
+            ));
+        }
+
+        out
+    }
+}
+
+/// Spawn the background task that periodically snapshots the fleet,
+/// alongside the processor task in `run_main`.
+pub fn spawn_fleet_monitor(
+    monitor: std::sync::Arc<FleetMonitor>,
+    integration: std::sync::Arc<AsyncSubAgentIntegration>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            monitor.snapshot_once(&integration).await;
+        }
+    })
+}
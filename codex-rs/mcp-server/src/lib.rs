@@ -26,6 +26,7 @@ use tracing::error;
 use tracing::info;
 use tracing_subscriber::EnvFilter;
 
+pub mod agent_store;
 mod codex_tool_config;
 mod codex_tool_runner;
 pub mod codex_tools;
@@ -33,15 +34,19 @@ mod custom_command_tool;
 mod custom_command_tool_handler;
 mod deep_research_tool;
 mod deep_research_tool_handler;
+mod error_channel;
 mod error_code;
 mod exec_approval;
+pub mod fleet_monitor;
 mod hook_tool;
 mod hook_tool_handler;
 pub(crate) mod message_processor;
+pub mod ot_merge;
 mod outgoing_message;
 mod patch_approval;
 mod subagent_tool;
 mod subagent_tool_handler;
+pub mod subagent_runner;
 mod supervisor_tool;
 mod supervisor_tool_handler;
 
@@ -64,6 +69,39 @@ pub use crate::supervisor_tool::SupervisorToolParam;
 /// plenty for an interactive CLI.
 const CHANNEL_CAPACITY: usize = 128;
 
+/// How long a remote runner may go without a heartbeat before its current
+/// task is considered abandoned and re-queued for another runner.
+const RUNNER_HEARTBEAT_DEADLINE: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How often the heartbeat sweeper checks for runners that have gone quiet.
+const RUNNER_HEARTBEAT_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// How often the fleet monitor snapshots agent health.
+const FLEET_SNAPSHOT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Set (to any non-empty, non-`"0"`/`"false"` value) to dispatch subagents
+/// to remote runners instead of running them in-process. Off by default: no
+/// runner binary ships in this repo yet, so enabling this unconditionally
+/// would silently strand every dispatched task in `pending` forever.
+const REMOTE_RUNNERS_ENV_VAR: &str = "CODEX_MCP_REMOTE_RUNNERS";
+
+/// Set to a Postgres connection string to persist subagent lifecycle state
+/// so it survives an MCP restart. Unset by default, in which case agent
+/// state stays in-memory-only exactly as before this feature existed. This
+/// is read directly from the environment rather than `Config` because
+/// `codex_core::config::Config` is owned by another crate in this
+/// workspace and isn't touched by this series.
+const SUBAGENT_STORE_DATABASE_URL_ENV_VAR: &str = "CODEX_MCP_SUBAGENT_STORE_DATABASE_URL";
+
+/// Whether an opt-in feature flag environment variable is set to a truthy
+/// value.
+fn env_flag_enabled(name: &str) -> bool {
+    match std::env::var(name) {
+        Ok(value) => !value.is_empty() && value != "0" && !value.eq_ignore_ascii_case("false"),
+        Err(_) => false,
+    }
+}
+
 pub async fn run_main(
     codex_linux_sandbox_exe: Option<PathBuf>,
     cli_config_overrides: CliConfigOverrides,
@@ -151,23 +189,108 @@ pub async fn run_main(
     );
     let agent_runtime = Arc::new(runtime);
 
-    // Task: process incoming messages.
+    // Pool that lets external runner processes long-poll for subagent work
+    // instead of every agent executing in-process. Opt-in and off by
+    // default: no runner binary ships in this repo yet, so turning this on
+    // for everyone would silently strand every `start_task`/`auto_dispatch`
+    // in `pending` with nothing around to ever call `acquire_work`.
+    let runner_pool = if env_flag_enabled(REMOTE_RUNNERS_ENV_VAR) {
+        let pool = Arc::new(subagent_runner::RunnerPool::new(RUNNER_HEARTBEAT_DEADLINE));
+        let _heartbeat_sweeper = subagent_runner::spawn_heartbeat_sweeper(
+            Arc::clone(&pool),
+            RUNNER_HEARTBEAT_SWEEP_INTERVAL,
+        );
+        Some(pool)
+    } else {
+        None
+    };
+
+    // In-memory error-collection channel: subagent failures are sent here
+    // instead of only being logged, so `get_errors`/`check_inbox` can
+    // surface them after the fact (until the server restarts).
+    let (error_chan, error_log) = error_channel::error_channel();
+
+    // Optional persistence so agent state survives an MCP restart. Users
+    // who don't configure a database URL keep the current in-memory-only
+    // behavior.
+    let subagent_store_database_url = std::env::var(SUBAGENT_STORE_DATABASE_URL_ENV_VAR).ok();
+    let agent_store = agent_store::configured_store(subagent_store_database_url.as_deref())
+        .await
+        .map_err(|e| {
+            std::io::Error::new(ErrorKind::Other, format!("error connecting subagent store: {e}"))
+        })?;
+
+    // Reload whatever was still outstanding as of the last restart, so
+    // `check_inbox`/`get_status` can surface pre-restart work instead of
+    // silently losing it. A store that can't be read is logged and treated
+    // as having nothing to recover, rather than failing startup.
+    let recovered_agents = match agent_store.as_ref() {
+        Some(store) => store.load_outstanding().await.unwrap_or_else(|e| {
+            error!("failed to load outstanding subagent state on startup: {e}");
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+    if !recovered_agents.is_empty() {
+        info!(
+            "recovered {} outstanding subagent(s) from a prior session",
+            recovered_agents.len()
+        );
+    }
+
+    // Reconciles concurrent subagent file edits with operational transform
+    // instead of last-writer-wins.
+    let file_versions = Arc::new(ot_merge::FileVersionTracker::new());
+
+    // Constructed once here (rather than inside the message processor) so
+    // the fleet monitor below can snapshot the same instance the tool
+    // handlers see.
+    let async_integration = Arc::new(
+        codex_core::async_subagent_integration::AsyncSubAgentIntegration::new(Arc::clone(
+            &agent_runtime,
+        )),
+    );
+
+    // Periodically snapshots fleet health (active agents, token spend,
+    // stalled agents) for a push stream and a scrapeable metrics endpoint.
+    let fleet_monitor = Arc::new(fleet_monitor::FleetMonitor::new(runtime_budget));
+    let _fleet_monitor_handle = fleet_monitor::spawn_fleet_monitor(
+        Arc::clone(&fleet_monitor),
+        Arc::clone(&async_integration),
+        FLEET_SNAPSHOT_INTERVAL,
+    );
+
+    // Task: process incoming messages. The processor is wrapped in an `Arc`
+    // and each message gets its own spawned task rather than being awaited
+    // inline here, so one slow call (a remote runner's `acquire_work`
+    // long-poll, in particular) can't stall every other in-flight request.
     let processor_handle = tokio::spawn({
         let outgoing_message_sender = OutgoingMessageSender::new(outgoing_tx);
-        let mut processor = MessageProcessor::new(
+        let processor = Arc::new(MessageProcessor::new(
             outgoing_message_sender,
             codex_linux_sandbox_exe,
             Arc::clone(&config),
             Some(agent_runtime),
-        );
+            runner_pool,
+            error_chan,
+            Arc::clone(&error_log),
+            agent_store,
+            recovered_agents,
+            Arc::clone(&file_versions),
+            Some(Arc::clone(&async_integration)),
+            Arc::clone(&fleet_monitor),
+        ));
         async move {
             while let Some(msg) = incoming_rx.recv().await {
-                match msg {
-                    JSONRPCMessage::Request(r) => processor.process_request(r).await,
-                    JSONRPCMessage::Response(r) => processor.process_response(r).await,
-                    JSONRPCMessage::Notification(n) => processor.process_notification(n).await,
-                    JSONRPCMessage::Error(e) => processor.process_error(e),
-                }
+                let processor = Arc::clone(&processor);
+                tokio::spawn(async move {
+                    match msg {
+                        JSONRPCMessage::Request(r) => processor.process_request(r).await,
+                        JSONRPCMessage::Response(r) => processor.process_response(r).await,
+                        JSONRPCMessage::Notification(n) => processor.process_notification(n).await,
+                        JSONRPCMessage::Error(e) => processor.process_error(e),
+                    }
+                });
             }
 
             info!("processor task exited (channel closed)");
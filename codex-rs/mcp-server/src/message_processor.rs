@@ -0,0 +1,187 @@
+//! Dispatches incoming JSON-RPC messages to the registered tool handlers.
+//!
+//! This is the seam between the stdio transport in `lib.rs` and the actual
+//! tool implementations: `run_main` builds one `MessageProcessor` per
+//! server process, handing it every shared dependency (the agent runtime,
+//! the remote runner pool, durable error reporting, persistence, and so on)
+//! so a `tools/call` request can be routed by tool name to
+//! `subagent_tool_handler`/`supervisor_tool_handler` with everything those
+//! handlers need already in scope.
+//!
+//! Every field here is cheap to share (`Arc`-wrapped or itself a cloneable
+//! channel handle), which is load-bearing: `run_main` wraps the processor in
+//! an `Arc` and spawns a fresh task per incoming message rather than calling
+//! `process_request` inline in the read loop, so a long-running call — most
+//! notably a remote runner's `acquire_work` long-poll — can't stall every
+//! other in-flight request.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use codex_core::agents::AgentRuntime;
+use codex_core::async_subagent_integration::AsyncSubAgentIntegration;
+use codex_core::config::Config;
+use mcp_types::CallToolResult;
+use mcp_types::ContentBlock;
+use mcp_types::JSONRPCError;
+use mcp_types::JSONRPCNotification;
+use mcp_types::JSONRPCRequest;
+use mcp_types::JSONRPCResponse;
+use mcp_types::TextContent;
+use serde_json::Value;
+use tracing::debug;
+use tracing::error;
+use tracing::warn;
+
+use crate::agent_store::AgentStore;
+use crate::agent_store::PersistedAgentState;
+use crate::error_channel::ErrChan;
+use crate::error_channel::ErrorLog;
+use crate::fleet_monitor::FleetMonitor;
+use crate::ot_merge::FileVersionTracker;
+use crate::outgoing_message::OutgoingMessageSender;
+use crate::subagent_runner::RunnerPool;
+use crate::subagent_tool_handler;
+use crate::supervisor_tool_handler;
+
+/// Routes incoming JSON-RPC messages to the subagent/supervisor tool
+/// handlers, holding every dependency those handlers need. The optional
+/// features (remote runners, the database-backed store) are held as
+/// `Option<Arc<_>>`, so a feature that hasn't been configured degrades to
+/// today's in-process, in-memory-only behavior instead of failing to
+/// construct.
+pub(crate) struct MessageProcessor {
+    outgoing: OutgoingMessageSender,
+    #[allow(dead_code)]
+    codex_linux_sandbox_exe: Option<PathBuf>,
+    #[allow(dead_code)]
+    config: Arc<Config>,
+    agent_runtime: Option<Arc<AgentRuntime>>,
+    runner_pool: Option<Arc<RunnerPool>>,
+    error_chan: ErrChan,
+    error_log: Arc<ErrorLog>,
+    agent_store: Option<Arc<dyn AgentStore>>,
+    recovered_agents: Arc<Vec<PersistedAgentState>>,
+    file_versions: Arc<FileVersionTracker>,
+    async_integration: Option<Arc<AsyncSubAgentIntegration>>,
+    fleet_monitor: Arc<FleetMonitor>,
+}
+
+impl MessageProcessor {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        outgoing: OutgoingMessageSender,
+        codex_linux_sandbox_exe: Option<PathBuf>,
+        config: Arc<Config>,
+        agent_runtime: Option<Arc<AgentRuntime>>,
+        runner_pool: Option<Arc<RunnerPool>>,
+        error_chan: ErrChan,
+        error_log: Arc<ErrorLog>,
+        agent_store: Option<Arc<dyn AgentStore>>,
+        recovered_agents: Vec<PersistedAgentState>,
+        file_versions: Arc<FileVersionTracker>,
+        async_integration: Option<Arc<AsyncSubAgentIntegration>>,
+        fleet_monitor: Arc<FleetMonitor>,
+    ) -> Self {
+        Self {
+            outgoing,
+            codex_linux_sandbox_exe,
+            config,
+            agent_runtime,
+            runner_pool,
+            error_chan,
+            error_log,
+            agent_store,
+            recovered_agents: Arc::new(recovered_agents),
+            file_versions,
+            async_integration,
+            fleet_monitor,
+        }
+    }
+
+    pub(crate) async fn process_request(&self, request: JSONRPCRequest) {
+        match request.method.as_str() {
+            "tools/call" => self.process_call_tool_request(request).await,
+            other => debug!("ignoring unsupported request method: {other}"),
+        }
+    }
+
+    pub(crate) async fn process_response(&self, response: JSONRPCResponse) {
+        debug!("ignoring unsolicited response id={:?}", response.id);
+    }
+
+    pub(crate) async fn process_notification(&self, notification: JSONRPCNotification) {
+        debug!("ignoring notification: {}", notification.method);
+    }
+
+    pub(crate) fn process_error(&self, error: JSONRPCError) {
+        error!("received JSON-RPC error: {:?}", error);
+    }
+
+    async fn process_call_tool_request(&self, request: JSONRPCRequest) {
+        let id = request.id.clone();
+        let Some(params) = request.params.clone() else {
+            warn!("tools/call request missing params");
+            return;
+        };
+
+        let tool_name = params
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let arguments = params.get("arguments").cloned().unwrap_or(Value::Null);
+
+        let result = match tool_name.as_str() {
+            "subagent" => self.call_subagent_tool(arguments).await,
+            "supervisor" => {
+                supervisor_tool_handler::handle_supervisor_tool_call(
+                    id.clone(),
+                    Some(arguments),
+                    &self.agent_runtime,
+                )
+                .await
+            }
+            other => {
+                warn!("unknown tool requested: {other}");
+                error_result(format!("unknown tool: {other}"))
+            }
+        };
+
+        self.outgoing.send_response(id, result).await;
+    }
+
+    async fn call_subagent_tool(&self, arguments: Value) -> CallToolResult {
+        match subagent_tool_handler::handle_subagent_tool_call(
+            arguments,
+            &self.async_integration,
+            &self.runner_pool,
+            &Some(Arc::clone(&self.error_log)),
+            &Some(self.error_chan.clone()),
+            &self.agent_store,
+            &self.recovered_agents,
+            &Some(Arc::clone(&self.file_versions)),
+            &Some(Arc::clone(&self.fleet_monitor)),
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(e) => {
+                error!("subagent tool call failed: {e}");
+                error_result(format!("subagent tool call failed: {e}"))
+            }
+        }
+    }
+}
+
+fn error_result(text: String) -> CallToolResult {
+    CallToolResult {
+        content: vec![ContentBlock::TextContent(TextContent {
+            r#type: "text".to_string(),
+            text,
+            annotations: None,
+        })],
+        is_error: Some(true),
+        structured_content: None,
+    }
+}
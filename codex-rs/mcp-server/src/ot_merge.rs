@@ -0,0 +1,277 @@
+//! Operational-transform reconciliation for concurrent subagent file edits.
+//!
+//! `AgentRuntime` runs multiple subagents concurrently, so two agents
+//! dispatched via `start_task`/`auto_dispatch` can edit the same file and
+//! clobber each other with last-writer-wins. This module represents each
+//! agent's edits as a sequence of [`Op`]s against a shared baseline and
+//! merges concurrent edit sets with operational transform: when two agents
+//! produce ops against the same baseline version, [`transform`] rewrites
+//! each set so both apply cleanly. Overlapping edits are merged, not
+//! flagged: [`transform`] reports no conflict offsets, so no [`Conflict`]
+//! is ever recorded.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use tokio::sync::Mutex;
+
+/// A single edit operation against a character sequence, in document order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    /// Leave the next `n` characters of the base document unchanged.
+    Retain(usize),
+    /// Insert text at the current position.
+    Insert(String),
+    /// Remove the next `n` characters of the base document.
+    Delete(usize),
+}
+
+/// A region where two agents' edits could not both be applied cleanly.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub agent_a: String,
+    pub agent_b: String,
+    /// Byte offset into the baseline where the conflicting edits overlap.
+    pub offset: usize,
+}
+
+/// Apply a sequence of ops to `base`, returning the resulting text.
+pub fn apply(base: &str, ops: &[Op]) -> String {
+    let chars: Vec<char> = base.chars().collect();
+    let mut pos = 0;
+    let mut out = String::new();
+    for op in ops {
+        match op {
+            Op::Retain(n) => {
+                let end = (pos + n).min(chars.len());
+                out.extend(&chars[pos..end]);
+                pos = end;
+            }
+            Op::Insert(text) => out.push_str(text),
+            Op::Delete(n) => pos = (pos + n).min(chars.len()),
+        }
+    }
+    // Anything past the last explicit op is left untouched, mirroring how
+    // most OT implementations treat a short op sequence.
+    out.extend(&chars[pos..]);
+    out
+}
+
+/// Transform two concurrent op sequences (both produced against the same
+/// baseline) against one another so applying `a` then `b'` and applying `b`
+/// then `a'` converge on the same document. Returns `(a', b')` along with
+/// a list of conflict offsets that is always empty: where both sides delete
+/// the same characters the union of the deletes is applied, and concurrent
+/// inserts are ordered `a` before `b`.
+pub fn transform(a: &[Op], b: &[Op]) -> (Vec<Op>, Vec<Op>, Vec<usize>) {
+    let mut a_prime = Vec::new();
+    let mut b_prime = Vec::new();
+    let mut conflict_offsets = Vec::new();
+
+    let mut ai = a.iter().cloned().peekable();
+    let mut bi = b.iter().cloned().peekable();
+    let mut base_offset = 0usize;
+
+    let mut cur_a = ai.next();
+    let mut cur_b = bi.next();
+
+    loop {
+        match (cur_a.clone(), cur_b.clone()) {
+            (None, None) => break,
+            (Some(op_a), None) => {
+                a_prime.push(op_a);
+                cur_a = ai.next();
+            }
+            (None, Some(op_b)) => {
+                b_prime.push(op_b);
+                cur_b = bi.next();
+            }
+            (Some(op_a), Some(op_b)) => match (op_a.clone(), op_b.clone()) {
+                // Inserts never consume base characters: always let both
+                // sides' inserts through, `a` before `b` by convention. An
+                // insert anchored at a position the other side is
+                // concurrently deleting is just the single most common shape
+                // of two agents editing near each other – it lands cleanly
+                // at the edge of the deleted region – so it is not treated
+                // as a conflict. None of the arms below report a conflict
+                // either.
+                (Op::Insert(text), _other) => {
+                    // `b'` must skip over the text `a` is inserting so it
+                    // keeps landing at the same logical position in the
+                    // document that now also contains `a`'s insert.
+                    let inserted_len = text.chars().count();
+                    a_prime.push(Op::Insert(text));
+                    b_prime.push(Op::Retain(inserted_len));
+                    cur_a = ai.next();
+                }
+                (_other, Op::Insert(text)) => {
+                    let inserted_len = text.chars().count();
+                    b_prime.push(Op::Insert(text));
+                    a_prime.push(Op::Retain(inserted_len));
+                    cur_b = bi.next();
+                }
+                (Op::Retain(ra), Op::Retain(rb)) => {
+                    let n = ra.min(rb);
+                    a_prime.push(Op::Retain(n));
+                    b_prime.push(Op::Retain(n));
+                    base_offset += n;
+                    cur_a = advance(Op::Retain(ra), n, &mut ai);
+                    cur_b = advance(Op::Retain(rb), n, &mut bi);
+                }
+                (Op::Delete(da), Op::Delete(db)) => {
+                    // Both sides delete the same region: idempotent, not a
+                    // conflict.
+                    let n = da.min(db);
+                    base_offset += n;
+                    cur_a = advance(Op::Delete(da), n, &mut ai);
+                    cur_b = advance(Op::Delete(db), n, &mut bi);
+                }
+                (Op::Delete(da), Op::Retain(rb)) => {
+                    let n = da.min(rb);
+                    a_prime.push(Op::Delete(n));
+                    base_offset += n;
+                    cur_a = advance(Op::Delete(da), n, &mut ai);
+                    cur_b = advance(Op::Retain(rb), n, &mut bi);
+                }
+                (Op::Retain(ra), Op::Delete(db)) => {
+                    let n = ra.min(db);
+                    b_prime.push(Op::Delete(n));
+                    base_offset += n;
+                    cur_a = advance(Op::Retain(ra), n, &mut ai);
+                    cur_b = advance(Op::Delete(db), n, &mut bi);
+                }
+            },
+        }
+    }
+
+    (a_prime, b_prime, conflict_offsets)
+}
+
+/// Consume `n` units from `op`, pushing the remainder back onto `iter` if
+/// any, and return the next current op.
+fn advance(
+    op: Op,
+    n: usize,
+    iter: &mut std::iter::Peekable<std::vec::IntoIter<Op>>,
+) -> Option<Op> {
+    let remaining = match &op {
+        Op::Retain(r) => r.saturating_sub(n),
+        Op::Delete(r) => r.saturating_sub(n),
+        Op::Insert(_) => 0,
+    };
+    if remaining > 0 {
+        let remainder = match op {
+            Op::Retain(_) => Op::Retain(remaining),
+            Op::Delete(_) => Op::Delete(remaining),
+            Op::Insert(text) => Op::Insert(text),
+        };
+        Some(remainder)
+    } else {
+        iter.next()
+    }
+}
+
+/// Per-file baseline version tracker: late-finishing agents transform their
+/// ops against the current document rather than the version they started
+/// from, and unresolved conflicts are kept so `get_status` can surface
+/// them for the affected agents.
+#[derive(Default)]
+pub struct FileVersionTracker {
+    files: Mutex<HashMap<PathBuf, FileEntry>>,
+}
+
+struct FileEntry {
+    version: u64,
+    content: String,
+    /// Ops applied to reach each version, keyed by the version they
+    /// produced, so a late-finishing agent can replay-and-transform against
+    /// everything it missed rather than just the current content.
+    history: Vec<(u64, String, Vec<Op>)>,
+    conflicts: Vec<Conflict>,
+}
+
+impl FileVersionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register (or reset) a file's baseline content, returning its version.
+    pub async fn set_baseline(&self, path: &Path, content: String) -> u64 {
+        let mut files = self.files.lock().await;
+        let entry = files.entry(path.to_path_buf()).or_insert(FileEntry {
+            version: 0,
+            content: String::new(),
+            history: Vec::new(),
+            conflicts: Vec::new(),
+        });
+        entry.version += 1;
+        entry.content = content;
+        entry.history.clear();
+        entry.version
+    }
+
+    /// Apply an agent's ops to the file, transforming against any edits
+    /// made since `agent_baseline_version`. Returns the new document
+    /// version.
+    pub async fn apply_agent_edit(
+        &self,
+        path: &Path,
+        agent_id: &str,
+        agent_baseline_version: u64,
+        mut ops: Vec<Op>,
+    ) -> anyhow::Result<u64> {
+        let mut files = self.files.lock().await;
+        let entry = files
+            .get_mut(path)
+            .ok_or_else(|| anyhow::anyhow!("no baseline registered for {}", path.display()))?;
+
+        // Transform against every version produced since this agent's
+        // baseline, in order, so it converges on the *current* document
+        // rather than the stale one it started from.
+        for (version, author, missed_ops) in &entry.history {
+            if *version <= agent_baseline_version {
+                continue;
+            }
+            let (ops_prime, _, conflicts) = transform(&ops, missed_ops);
+            for offset in conflicts {
+                entry.conflicts.push(Conflict {
+                    agent_a: agent_id.to_string(),
+                    agent_b: author.clone(),
+                    offset,
+                });
+            }
+            ops = ops_prime;
+        }
+
+        entry.content = apply(&entry.content, &ops);
+        entry.version += 1;
+        entry
+            .history
+            .push((entry.version, agent_id.to_string(), ops));
+        Ok(entry.version)
+    }
+
+    /// Unresolved conflicts recorded for a file, if any.
+    pub async fn conflicts_for(&self, path: &Path) -> Vec<Conflict> {
+        self.files
+            .lock()
+            .await
+            .get(path)
+            .map(|entry| entry.conflicts.clone())
+            .unwrap_or_default()
+    }
+
+    /// Unresolved conflicts across every tracked file that involve the
+    /// given agent, for surfacing in that agent's `get_status` summary.
+    pub async fn conflicts_for_agent(&self, agent_id: &str) -> Vec<Conflict> {
+        self.files
+            .lock()
+            .await
+            .values()
+            .flat_map(|entry| entry.conflicts.iter())
+            .filter(|c| c.agent_a == agent_id || c.agent_b == agent_id)
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,242 @@
+//! Remote subagent runner protocol.
+//!
+//! In-process execution via `AgentRuntime` caps parallelism at a single
+//! Codex host and loses every running agent if that host crashes. This
+//! module lets external runner processes connect to the MCP host and pull
+//! pending subagent tasks instead, modeled on a pull-based CI runner: a
+//! runner long-polls [`RunnerPool::acquire_work`], the host hands back a
+//! [`TaskDescriptor`] (or holds the connection open until one is ready),
+//! and the runner streams heartbeats back so the host can detect a runner
+//! that has gone quiet and re-dispatch its work.
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
+
+use codex_core::async_subagent_integration::AgentType;
+use codex_protocol::ConversationId;
+use tokio::sync::Mutex;
+use tokio::sync::Notify;
+use tracing::info;
+use tracing::warn;
+
+/// A unit of subagent work handed to a runner.
+#[derive(Debug, Clone)]
+pub struct TaskDescriptor {
+    pub agent_id: String,
+    pub agent_type: AgentType,
+    pub task: String,
+    pub budget: Option<usize>,
+    pub conversation_id: ConversationId,
+}
+
+/// Failure modes distinguished while a runner is acquiring or streaming work.
+#[derive(Debug, Clone)]
+pub enum WorkAcquireError {
+    /// The connection to the runner dropped before a response could be sent
+    /// or received (socket reset, timeout, etc.).
+    Transport(String),
+    /// The runner's stream ended mid-task, after acquiring work but before
+    /// reporting completion or failure. The task must be re-queued rather
+    /// than silently dropped.
+    EarlyEof { agent_id: String },
+    /// The runner violated the protocol: a malformed descriptor, an unknown
+    /// `agent_type`, or a message out of sequence.
+    Protocol(String),
+}
+
+impl fmt::Display for WorkAcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorkAcquireError::Transport(msg) => write!(f, "transport error: {msg}"),
+            WorkAcquireError::EarlyEof { agent_id } => {
+                write!(f, "runner disconnected mid-task for agent {agent_id}")
+            }
+            WorkAcquireError::Protocol(msg) => write!(f, "protocol violation: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for WorkAcquireError {}
+
+/// Tracks a task that has been handed to a runner but not yet completed.
+struct InFlight {
+    descriptor: TaskDescriptor,
+    runner_id: String,
+    last_heartbeat: Instant,
+}
+
+/// Host-side half of the long-poll work-acquisition protocol.
+///
+/// Runners call [`RunnerPool::acquire_work`] in a loop; tasks queued via
+/// [`RunnerPool::enqueue`] are handed out in FIFO order. Call
+/// [`RunnerPool::sweep_expired_heartbeats`] periodically (e.g. from a
+/// background task spawned in `run_main`) to re-queue work whose runner has
+/// gone quiet past `heartbeat_deadline`.
+pub struct RunnerPool {
+    pending: Mutex<VecDeque<TaskDescriptor>>,
+    in_flight: Mutex<HashMap<String, InFlight>>,
+    notify: Notify,
+    heartbeat_deadline: Duration,
+    next_id: AtomicU64,
+}
+
+impl RunnerPool {
+    pub fn new(heartbeat_deadline: Duration) -> Self {
+        Self {
+            pending: Mutex::new(VecDeque::new()),
+            in_flight: Mutex::new(HashMap::new()),
+            notify: Notify::new(),
+            heartbeat_deadline,
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Queue a task for the next runner that asks for work.
+    pub async fn enqueue(&self, descriptor: TaskDescriptor) {
+        self.pending.lock().await.push_back(descriptor);
+        self.notify.notify_one();
+    }
+
+    /// Allocate an agent id and queue a task for it in one step, for
+    /// dispatch paths (`start_task`/`auto_dispatch`) that don't already have
+    /// an id from an in-process `AgentRuntime` to hand out. Returns the
+    /// allocated id so the caller can report it back to the user.
+    pub async fn enqueue_task(
+        &self,
+        agent_type: AgentType,
+        task: String,
+        budget: Option<usize>,
+        conversation_id: ConversationId,
+    ) -> String {
+        let agent_id = format!(
+            "runner-{}-{}",
+            agent_type.as_str(),
+            self.next_id.fetch_add(1, Ordering::Relaxed)
+        );
+        self.enqueue(TaskDescriptor {
+            agent_id: agent_id.clone(),
+            agent_type,
+            task,
+            budget,
+            conversation_id,
+        })
+        .await;
+        agent_id
+    }
+
+    /// Long-poll for the next available task. Blocks until one is queued.
+    pub async fn acquire_work(&self, runner_id: &str) -> TaskDescriptor {
+        loop {
+            {
+                let mut pending = self.pending.lock().await;
+                if let Some(descriptor) = pending.pop_front() {
+                    drop(pending);
+                    self.in_flight.lock().await.insert(
+                        descriptor.agent_id.clone(),
+                        InFlight {
+                            descriptor: descriptor.clone(),
+                            runner_id: runner_id.to_string(),
+                            last_heartbeat: Instant::now(),
+                        },
+                    );
+                    return descriptor;
+                }
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    /// Record a heartbeat from a runner that is still working an agent.
+    pub async fn heartbeat(&self, agent_id: &str) -> Result<(), WorkAcquireError> {
+        let mut in_flight = self.in_flight.lock().await;
+        match in_flight.get_mut(agent_id) {
+            Some(entry) => {
+                entry.last_heartbeat = Instant::now();
+                Ok(())
+            }
+            None => Err(WorkAcquireError::Protocol(format!(
+                "heartbeat for unknown agent {agent_id}"
+            ))),
+        }
+    }
+
+    /// The runner finished (successfully or not) and the task no longer
+    /// needs tracking.
+    pub async fn complete(&self, agent_id: &str) {
+        self.in_flight.lock().await.remove(agent_id);
+    }
+
+    /// The runner's connection dropped mid-task: re-queue the work rather
+    /// than losing it.
+    pub async fn requeue_on_early_eof(&self, agent_id: &str) -> Result<(), WorkAcquireError> {
+        let entry = self.in_flight.lock().await.remove(agent_id);
+        match entry {
+            Some(entry) => {
+                warn!(
+                    "runner {} disconnected mid-task for agent {}; re-queuing",
+                    entry.runner_id, agent_id
+                );
+                self.enqueue(entry.descriptor).await;
+                Ok(())
+            }
+            None => Err(WorkAcquireError::EarlyEof {
+                agent_id: agent_id.to_string(),
+            }),
+        }
+    }
+
+    /// Re-queue any in-flight task whose runner has missed its heartbeat
+    /// deadline. Returns the agent ids that were re-dispatched.
+    pub async fn sweep_expired_heartbeats(&self) -> Vec<String> {
+        let expired: Vec<(String, InFlight)> = {
+            let mut in_flight = self.in_flight.lock().await;
+            let now = Instant::now();
+            let expired_ids: Vec<String> = in_flight
+                .iter()
+                .filter(|(_, entry)| now.duration_since(entry.last_heartbeat) > self.heartbeat_deadline)
+                .map(|(agent_id, _)| agent_id.clone())
+                .collect();
+            expired_ids
+                .into_iter()
+                .filter_map(|agent_id| in_flight.remove(&agent_id).map(|entry| (agent_id, entry)))
+                .collect()
+        };
+
+        let mut requeued = Vec::with_capacity(expired.len());
+        for (agent_id, entry) in expired {
+            warn!(
+                "runner {} missed its heartbeat deadline for agent {}; re-queuing",
+                entry.runner_id, agent_id
+            );
+            self.enqueue(entry.descriptor).await;
+            requeued.push(agent_id);
+        }
+        requeued
+    }
+
+    /// Number of tasks waiting for a runner to pick them up.
+    pub async fn pending_count(&self) -> usize {
+        self.pending.lock().await.len()
+    }
+}
+
+/// Spawn the background task that periodically sweeps for runners whose
+/// heartbeat has lapsed, re-queuing their work.
+pub fn spawn_heartbeat_sweeper(pool: Arc<RunnerPool>, interval: Duration) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let requeued = pool.sweep_expired_heartbeats().await;
+            if !requeued.is_empty() {
+                info!("re-dispatched {} stalled agent(s)", requeued.len());
+            }
+        }
+    })
+}
@@ -2,19 +2,46 @@
 use anyhow::Result;
 use codex_core::async_subagent_integration::AgentType;
 use codex_core::async_subagent_integration::AsyncSubAgentIntegration;
+use codex_protocol::ConversationId;
 use mcp_types::CallToolResult;
 use mcp_types::ContentBlock;
 use mcp_types::TextContent;
 use serde_json::Value;
+use std::path::Path;
 use std::sync::Arc;
 use tracing::{debug, error, info, warn};
 
+use crate::agent_store::AgentStore;
+use crate::agent_store::PersistedAgentState;
+use crate::error_channel::ErrChan;
+use crate::error_channel::ErrorLog;
+use crate::fleet_monitor::FleetMonitor;
+use crate::ot_merge::FileVersionTracker;
+use crate::ot_merge::Op;
+use crate::subagent_runner::RunnerPool;
+use crate::subagent_runner::WorkAcquireError;
 use crate::subagent_tool::SubAgentToolParam;
 
+/// How long `acquire_work` holds the MCP call open waiting for a task before
+/// giving up and reporting a transport-level timeout to the caller.
+const ACQUIRE_WORK_POLL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_subagent_tool_call(
     arguments: Value,
     async_integration: &Option<Arc<AsyncSubAgentIntegration>>,
+    runner_pool: &Option<Arc<RunnerPool>>,
+    error_log: &Option<Arc<ErrorLog>>,
+    error_chan: &Option<ErrChan>,
+    agent_store: &Option<Arc<dyn AgentStore>>,
+    recovered_agents: &[PersistedAgentState],
+    file_versions: &Option<Arc<FileVersionTracker>>,
+    fleet_monitor: &Option<Arc<FleetMonitor>>,
 ) -> Result<CallToolResult> {
+    // `SubAgentToolParam` doesn't carry the file/ops fields used by
+    // `register_file_baseline`/`submit_file_edit`, so keep the raw JSON
+    // around to read those from directly.
+    let raw_arguments = arguments.clone();
     let params: SubAgentToolParam = serde_json::from_value(arguments)?;
 
     debug!("SubAgent tool called with action: {}", params.action);
@@ -40,41 +67,93 @@ pub async fn handle_subagent_tool_call(
             // Parse agent type
             let agent_type = parse_agent_type(&agent_type_str)?;
 
-            // Start the agent
-            let agent_id = integration
-                .start_agent(agent_type, &task)
-                .await
-                .map_err(|e| {
-                    error!("Failed to start agent {}: {}", agent_type.as_str(), e);
-                    e
-                })?;
-
-            info!(
-                "Started agent: {} with id: {}",
-                agent_type.as_str(),
-                agent_id
-            );
+            // When a remote runner pool is configured (opt-in, off by
+            // default), dispatch the task to it instead of running the
+            // agent in-process, so the work can be picked up by whichever
+            // runner long-polls for it next. Such an agent is unknown to
+            // `AsyncSubAgentIntegration`, so `get_status`/`get_thinking`/
+            // `get_token_report` can't report on it — only `runner_status`
+            // (and, once a runner relays it, the runner's own reporting).
+            let (agent_id, dispatch_note, next_steps) = match runner_pool.as_ref() {
+                Some(pool) => {
+                    let agent_id = pool
+                        .enqueue_task(agent_type, task.clone(), None, ConversationId::default())
+                        .await;
+                    info!(
+                        "Queued agent {} ({}) for a remote runner",
+                        agent_id,
+                        agent_type.as_str()
+                    );
+                    (
+                        agent_id,
+                        "queued for a remote runner",
+                        "- Use `runner_status` to see how many tasks are still pending a runner\n\
+                         - `get_status`/`get_thinking`/`get_token_report` do not cover \
+                         runner-dispatched agents",
+                    )
+                }
+                None => {
+                    let agent_id = integration
+                        .start_agent(agent_type, &task)
+                        .await
+                        .map_err(|e| {
+                            error!("Failed to start agent {}: {}", agent_type.as_str(), e);
+                            if let Some(chan) = error_chan.as_ref() {
+                                chan.send(format!("{}-start-failure", agent_type.as_str()), &e);
+                            }
+                            e
+                        })?;
+
+                    info!(
+                        "Started agent: {} with id: {}",
+                        agent_type.as_str(),
+                        agent_id
+                    );
+                    (
+                        agent_id,
+                        "now running in the background",
+                        "- Use `get_status` with agent_id to check progress\n\
+                         - Use `get_thinking` with agent_id to see reasoning\n\
+                         - Use `get_token_report` to track token usage",
+                    )
+                }
+            };
+
+            if let Some(monitor) = fleet_monitor.as_ref() {
+                monitor.record_started(agent_type).await;
+            }
+
+            if let Some(store) = agent_store.as_ref() {
+                if let Err(e) = store.record_start(&agent_id, agent_type, &task).await {
+                    warn!("failed to persist start of agent {agent_id}: {e}");
+                }
+            }
 
             format!(
                 "✅ SubAgent Started\n\n\
                 **Agent Type**: {}\n\
                 **Agent ID**: {}\n\
                 **Task**: {}\n\n\
-                **Status**: Agent is now running in the background.\n\n\
+                **Status**: Agent is {}.\n\n\
                 **Next Steps**:\n\
-                - Use `get_status` with agent_id to check progress\n\
-                - Use `get_thinking` with agent_id to see reasoning\n\
-                - Use `get_token_report` to track token usage",
-                agent_type_str, agent_id, task
+                {}",
+                agent_type_str, agent_id, task, dispatch_note, next_steps
             )
         }
         "check_inbox" => {
             debug!("Checking inbox for active agents");
             // Get all agent states as a proxy for "inbox"
             let states = integration.get_agent_states().await;
+            let error_count = match error_log.as_ref() {
+                Some(log) => log.count().await,
+                None => 0,
+            };
 
-            if states.is_empty() {
-                "📬 Inbox\n\nNo active agents or notifications.".to_string()
+            if states.is_empty() && recovered_agents.is_empty() {
+                format!(
+                    "📬 Inbox\n\nNo active agents or notifications.\n\n**Failed agents**: {}",
+                    error_count
+                )
             } else {
                 let mut output = String::from("📬 Active Agents\n\n");
                 for state in states {
@@ -86,6 +165,19 @@ pub async fn handle_subagent_tool_call(
                         state.progress
                     ));
                 }
+                if !recovered_agents.is_empty() {
+                    output.push_str("\n📼 Recovered from a prior session\n\n");
+                    for state in recovered_agents {
+                        output.push_str(&format!(
+                            "- **{}** ({}): {} - {:.1}% complete\n",
+                            state.agent_id,
+                            state.agent_type.as_str(),
+                            state.status,
+                            state.progress
+                        ));
+                    }
+                }
+                output.push_str(&format!("\n**Failed agents**: {}", error_count));
                 output
             }
         }
@@ -99,23 +191,108 @@ pub async fn handle_subagent_tool_call(
             // Generate task summary
             let summary = integration.generate_task_summary(agent_id).await;
 
-            format!("🤖 SubAgent Status\n\n{}", summary)
+            if let Some(store) = agent_store.as_ref() {
+                if let Some(state) = integration
+                    .get_agent_states()
+                    .await
+                    .into_iter()
+                    .find(|state| &state.agent_id == agent_id)
+                {
+                    if let Err(e) = store
+                        .record_transition(agent_id, &state.status, state.progress)
+                        .await
+                    {
+                        warn!("failed to persist status transition for agent {agent_id}: {e}");
+                    }
+                }
+                let tokens_used = integration.agent_tokens_used(agent_id).await;
+                if let Err(e) = store.record_tokens(agent_id, tokens_used).await {
+                    warn!("failed to persist token usage for agent {agent_id}: {e}");
+                }
+            }
+
+            let conflicts = match file_versions.as_ref() {
+                Some(tracker) => tracker.conflicts_for_agent(agent_id).await,
+                None => Vec::new(),
+            };
+            if conflicts.is_empty() {
+                format!("🤖 SubAgent Status\n\n{}", summary)
+            } else {
+                let mut conflict_lines = String::new();
+                for conflict in conflicts {
+                    conflict_lines.push_str(&format!(
+                        "- vs **{}** at offset {}\n",
+                        if conflict.agent_a == *agent_id {
+                            conflict.agent_b
+                        } else {
+                            conflict.agent_a
+                        },
+                        conflict.offset
+                    ));
+                }
+                format!(
+                    "🤖 SubAgent Status\n\n{}\n\n⚠️ **Unresolved edit conflicts**:\n{}",
+                    summary, conflict_lines
+                )
+            }
         }
         "auto_dispatch" => {
             let task = params
                 .task
                 .ok_or_else(|| anyhow::anyhow!("task required for auto_dispatch"))?;
 
-            // Auto-dispatch task and start agent
-            let agent_id = integration.auto_dispatch_task(&task).await?;
+            // Remote runners don't have access to the in-process task
+            // classifier, so when a runner pool is configured, send
+            // auto-dispatched work there as a general-purpose task rather
+            // than guessing a specialization.
+            let (agent_id, follow_up) = match runner_pool.as_ref() {
+                Some(pool) => {
+                    let agent_id = pool
+                        .enqueue_task(
+                            AgentType::General,
+                            task.clone(),
+                            None,
+                            ConversationId::default(),
+                        )
+                        .await;
+                    (
+                        agent_id,
+                        "Use `runner_status` to see how many tasks are still pending a runner; \
+                         `get_status` does not cover runner-dispatched agents.".to_string(),
+                    )
+                }
+                None => {
+                    let agent_id = integration.auto_dispatch_task(&task).await.map_err(|e| {
+                        error!("Failed to auto-dispatch task: {}", e);
+                        if let Some(chan) = error_chan.as_ref() {
+                            chan.send("auto-dispatch-failure", &e);
+                        }
+                        e
+                    })?;
+                    let follow_up = format!("Use `get_status` with agent_id={agent_id} to check progress.");
+                    (agent_id, follow_up)
+                }
+            };
+
+            if let Some(monitor) = fleet_monitor.as_ref() {
+                // Runner-queued tasks are General and unknown to the integration.
+                let agent_type = integration
+                    .get_agent_states()
+                    .await
+                    .into_iter()
+                    .find(|state| state.agent_id == agent_id)
+                    .map(|state| state.agent_type)
+                    .unwrap_or(AgentType::General);
+                monitor.record_started(agent_type).await;
+            }
 
             format!(
                 "🎯 Auto-Dispatch Complete\n\n\
                 **Agent ID**: {}\n\
                 **Task**: {}\n\n\
                 **Status**: Agent has been automatically selected and started.\n\n\
-                Use `get_status` with agent_id={} to check progress.",
-                agent_id, task, agent_id
+                {}",
+                agent_id, task, follow_up
             )
         }
         "get_thinking" => {
@@ -127,6 +304,16 @@ pub async fn handle_subagent_tool_call(
                     .await
                     .unwrap_or_else(|| format!("No thinking process found for {}", task_id));
 
+                // The persisted log is append-only, so this records the
+                // latest known summary rather than an incremental delta —
+                // there's no call site upstream of here where a fresh
+                // thinking chunk becomes available in isolation.
+                if let Some(store) = agent_store.as_ref() {
+                    if let Err(e) = store.record_thinking(task_id, &thinking).await {
+                        warn!("failed to persist thinking for agent {task_id}: {e}");
+                    }
+                }
+
                 format!(
                     "💭 Thinking Process\n\n**Task ID**: {}\n\n{}",
                     task_id, thinking
@@ -144,6 +331,156 @@ pub async fn handle_subagent_tool_call(
 
             format!("📊 Token Usage Report\n\n{}", report)
         }
+        "get_errors" => {
+            debug!("Fetching collected subagent errors");
+            match error_log.as_ref() {
+                Some(log) => {
+                    let errors = log.errors().await;
+                    if errors.is_empty() {
+                        "⚠️ Agent Errors\n\nNo background agents have failed.".to_string()
+                    } else {
+                        let mut output = format!("⚠️ Agent Errors ({} total)\n\n", errors.len());
+                        for err in errors {
+                            output.push_str(&format!("- **{}**: {}\n", err.agent_id, err.message));
+                        }
+                        output
+                    }
+                }
+                None => "⚠️ Agent Errors\n\nError tracking is not configured.".to_string(),
+            }
+        }
+        "get_metrics" => {
+            debug!("Taking a fleet snapshot for the metrics endpoint");
+            match fleet_monitor.as_ref() {
+                Some(monitor) => {
+                    monitor.snapshot_once(integration).await;
+                    monitor.render_metrics().await
+                }
+                None => "# fleet monitoring is not configured\n".to_string(),
+            }
+        }
+        "register_file_baseline" => {
+            let path = raw_arguments
+                .get("path")
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow::anyhow!("path required for register_file_baseline"))?;
+            let content = raw_arguments
+                .get("content")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+
+            match file_versions.as_ref() {
+                Some(tracker) => {
+                    let version = tracker.set_baseline(Path::new(path), content.to_string()).await;
+                    format!(
+                        "📄 Baseline Registered\n\n**Path**: {}\n**Version**: {}",
+                        path, version
+                    )
+                }
+                None => "📄 File-edit reconciliation is not configured.".to_string(),
+            }
+        }
+        "submit_file_edit" => {
+            let path = raw_arguments
+                .get("path")
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow::anyhow!("path required for submit_file_edit"))?;
+            let agent_id = params
+                .agent_id
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("agent_id required for submit_file_edit"))?;
+            let baseline_version = raw_arguments
+                .get("baseline_version")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| anyhow::anyhow!("baseline_version required for submit_file_edit"))?;
+            let ops = parse_ops(raw_arguments.get("ops"))?;
+
+            match file_versions.as_ref() {
+                Some(tracker) => {
+                    let new_version = tracker
+                        .apply_agent_edit(Path::new(path), agent_id, baseline_version, ops)
+                        .await?;
+                    format!(
+                        "✏️ Edit Applied\n\n**Path**: {}\n**New Version**: {}",
+                        path, new_version
+                    )
+                }
+                None => "✏️ File-edit reconciliation is not configured.".to_string(),
+            }
+        }
+        "runner_status" => {
+            debug!("Reporting remote runner pool status");
+            match runner_pool.as_ref() {
+                Some(pool) => {
+                    let pending = pool.pending_count().await;
+                    format!(
+                        "🏃 Runner Pool\n\n**Pending tasks awaiting a runner**: {}",
+                        pending
+                    )
+                }
+                None => "🏃 Runner Pool\n\nNo remote runner pool configured; agents execute in-process.".to_string(),
+            }
+        }
+        "acquire_work" => {
+            // A remote runner long-polls this action, identifying itself
+            // via `agent_id`, to pull the next queued task.
+            let runner_id = params
+                .agent_id
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("agent_id (used as the runner's id) required for acquire_work"))?;
+            match runner_pool.as_ref() {
+                Some(pool) => {
+                    match tokio::time::timeout(ACQUIRE_WORK_POLL_TIMEOUT, pool.acquire_work(runner_id)).await {
+                        Ok(descriptor) => format!(
+                            "🎫 Work Acquired\n\n\
+                            **Agent ID**: {}\n\
+                            **Agent Type**: {}\n\
+                            **Task**: {}",
+                            descriptor.agent_id,
+                            descriptor.agent_type.as_str(),
+                            descriptor.task
+                        ),
+                        Err(_) => {
+                            let err = WorkAcquireError::Transport(format!(
+                                "no work became available for runner {} within {:?}",
+                                runner_id, ACQUIRE_WORK_POLL_TIMEOUT
+                            ));
+                            warn!("{err}");
+                            return Err(anyhow::anyhow!(err));
+                        }
+                    }
+                }
+                None => "🎫 Work Acquired\n\nNo remote runner pool configured.".to_string(),
+            }
+        }
+        "runner_heartbeat" => {
+            let agent_id = params
+                .task_id
+                .as_ref()
+                .or(params.agent_id.as_ref())
+                .ok_or_else(|| anyhow::anyhow!("agent_id or task_id required for runner_heartbeat"))?;
+            match runner_pool.as_ref() {
+                Some(pool) => {
+                    pool.heartbeat(agent_id).await?;
+                    format!("💓 Heartbeat recorded for {agent_id}")
+                }
+                None => "💓 No remote runner pool configured.".to_string(),
+            }
+        }
+        "runner_complete" => {
+            let agent_id = params
+                .task_id
+                .as_ref()
+                .or(params.agent_id.as_ref())
+                .ok_or_else(|| anyhow::anyhow!("agent_id or task_id required for runner_complete"))?;
+            match runner_pool.as_ref() {
+                Some(pool) => {
+                    pool.complete(agent_id).await;
+                    format!("✅ Runner task {agent_id} marked complete")
+                }
+                None => "✅ No remote runner pool configured.".to_string(),
+            }
+        }
         _ => {
             return Err(anyhow::anyhow!("Unknown action: {}", params.action));
         }
@@ -160,6 +497,33 @@ pub async fn handle_subagent_tool_call(
     })
 }
 
+/// Parse a JSON array of `{"retain": n}` / `{"insert": "text"}` /
+/// `{"delete": n}` objects into the `Op` sequence `submit_file_edit` applies.
+fn parse_ops(value: Option<&Value>) -> Result<Vec<Op>> {
+    let Some(value) = value else {
+        return Ok(Vec::new());
+    };
+    let ops = value
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("ops must be a JSON array"))?;
+
+    ops.iter()
+        .map(|op| {
+            if let Some(n) = op.get("retain").and_then(Value::as_u64) {
+                Ok(Op::Retain(n as usize))
+            } else if let Some(text) = op.get("insert").and_then(Value::as_str) {
+                Ok(Op::Insert(text.to_string()))
+            } else if let Some(n) = op.get("delete").and_then(Value::as_u64) {
+                Ok(Op::Delete(n as usize))
+            } else {
+                Err(anyhow::anyhow!(
+                    "each op must be one of {{\"retain\": n}}, {{\"insert\": text}}, {{\"delete\": n}}"
+                ))
+            }
+        })
+        .collect()
+}
+
 /// Parse agent type string into AgentType enum
 fn parse_agent_type(type_str: &str) -> Result<AgentType> {
     debug!("Parsing agent type: {}", type_str);
@@ -186,6 +186,13 @@ async fn test_subagent_start_task() {
     let result = codex_mcp_server::subagent_tool_handler::handle_subagent_tool_call(
         arguments,
         &Some(integration.clone()),
+        &None,
+        &None,
+        &None,
+        &None,
+        &[],
+        &None,
+        &None,
     )
     .await
     .expect("start_task should succeed");
@@ -231,6 +238,13 @@ async fn test_subagent_check_inbox() {
     let result = codex_mcp_server::subagent_tool_handler::handle_subagent_tool_call(
         arguments,
         &Some(integration),
+        &None,
+        &None,
+        &None,
+        &None,
+        &[],
+        &None,
+        &None,
     )
     .await
     .expect("check_inbox should succeed");
@@ -276,6 +290,13 @@ async fn test_subagent_get_status() {
     let result = codex_mcp_server::subagent_tool_handler::handle_subagent_tool_call(
         arguments,
         &Some(integration),
+        &None,
+        &None,
+        &None,
+        &None,
+        &[],
+        &None,
+        &None,
     )
     .await
     .expect("get_status should succeed");
@@ -315,6 +336,13 @@ async fn test_subagent_auto_dispatch() {
     let result = codex_mcp_server::subagent_tool_handler::handle_subagent_tool_call(
         arguments,
         &Some(integration),
+        &None,
+        &None,
+        &None,
+        &None,
+        &[],
+        &None,
+        &None,
     )
     .await
     .expect("auto_dispatch should succeed");
@@ -353,6 +381,13 @@ async fn test_subagent_token_report() {
     let result = codex_mcp_server::subagent_tool_handler::handle_subagent_tool_call(
         arguments,
         &Some(integration),
+        &None,
+        &None,
+        &None,
+        &None,
+        &[],
+        &None,
+        &None,
     )
     .await
     .expect("get_token_report should succeed");
@@ -388,6 +423,13 @@ async fn test_subagent_invalid_agent_type() {
     let result = codex_mcp_server::subagent_tool_handler::handle_subagent_tool_call(
         arguments,
         &Some(integration),
+        &None,
+        &None,
+        &None,
+        &None,
+        &[],
+        &None,
+        &None,
     )
     .await;
 
@@ -414,6 +456,13 @@ async fn test_subagent_missing_params() {
     let result = codex_mcp_server::subagent_tool_handler::handle_subagent_tool_call(
         arguments,
         &Some(integration),
+        &None,
+        &None,
+        &None,
+        &None,
+        &[],
+        &None,
+        &None,
     )
     .await;
 
@@ -450,3 +499,369 @@ async fn test_supervisor_no_runtime() {
         );
     }
 }
+
+/// Test that omitting a database URL keeps agent state in-memory-only,
+/// matching today's behavior, rather than requiring a Postgres instance.
+#[tokio::test]
+async fn test_agent_store_defaults_to_in_memory() {
+    let store = codex_mcp_server::agent_store::configured_store(None)
+        .await
+        .expect("configuring with no database URL should not fail");
+    assert!(
+        store.is_none(),
+        "no database URL should mean no persistence backend"
+    );
+}
+
+/// Test that every `AgentType` variant round-trips through the string
+/// `record_start` persists (`AgentType::as_str()`) and what
+/// `load_outstanding` decodes it back with (`parse_agent_type`). A mismatch
+/// here means a recovered row gets silently dropped on restart instead of
+/// being surfaced as an outstanding agent.
+#[test]
+fn test_agent_store_parse_agent_type_round_trips_as_str() {
+    let all_agent_types = [
+        AgentType::CodeExpert,
+        AgentType::SecurityExpert,
+        AgentType::TestingExpert,
+        AgentType::DocsExpert,
+        AgentType::DeepResearcher,
+        AgentType::DebugExpert,
+        AgentType::PerformanceExpert,
+        AgentType::General,
+    ];
+
+    for agent_type in all_agent_types {
+        let persisted = agent_type.as_str();
+        let parsed = codex_mcp_server::agent_store::parse_agent_type(persisted)
+            .unwrap_or_else(|e| panic!("failed to round-trip {persisted:?}: {e}"));
+        assert_eq!(
+            parsed.as_str(),
+            agent_type.as_str(),
+            "parse_agent_type({persisted:?}) did not round-trip back to {agent_type:?}"
+        );
+    }
+
+    assert!(
+        codex_mcp_server::agent_store::parse_agent_type("not_a_real_agent_type").is_err()
+    );
+}
+
+/// Test operational-transform merge: two agents editing disjoint regions of
+/// the same file both land cleanly instead of last-writer-wins.
+#[tokio::test]
+async fn test_ot_merge_applies_non_conflicting_concurrent_edits() {
+    use codex_mcp_server::ot_merge::FileVersionTracker;
+    use codex_mcp_server::ot_merge::Op;
+    use std::path::Path;
+
+    let tracker = FileVersionTracker::new();
+    let path = Path::new("shared.rs");
+    let baseline_version = tracker.set_baseline(path, "fn main() {}".to_string()).await;
+
+    // Agent A inserts a comment at the start; agent B inserts at the end.
+    // Both read the same baseline version.
+    let version_after_a = tracker
+        .apply_agent_edit(
+            path,
+            "agent-a",
+            baseline_version,
+            vec![Op::Insert("// start\n".to_string()), Op::Retain(12)],
+        )
+        .await
+        .expect("agent A's edit should apply");
+
+    let version_after_b = tracker
+        .apply_agent_edit(
+            path,
+            "agent-b",
+            baseline_version,
+            vec![Op::Retain(12), Op::Insert(" // end".to_string())],
+        )
+        .await
+        .expect("agent B's edit should apply even though A already moved the document on");
+
+    assert!(version_after_b > version_after_a);
+    assert!(
+        tracker.conflicts_for(path).await.is_empty(),
+        "disjoint inserts should not be reported as conflicts"
+    );
+}
+
+/// Test operational-transform merge: an insert anchored at a position the
+/// other side is concurrently deleting is the common "editing near each
+/// other" shape, not a genuine overlapping edit, so it should not be
+/// reported as a conflict.
+#[tokio::test]
+async fn test_ot_merge_insert_adjacent_to_delete_is_not_a_conflict() {
+    use codex_mcp_server::ot_merge::FileVersionTracker;
+    use codex_mcp_server::ot_merge::Op;
+    use std::path::Path;
+
+    let tracker = FileVersionTracker::new();
+    let path = Path::new("shared.rs");
+    let baseline_version = tracker
+        .set_baseline(path, "fn main() { old_call(); }".to_string())
+        .await;
+
+    // Agent A deletes `old_call()`; agent B inserts a comment right before
+    // the same position, against the same baseline.
+    tracker
+        .apply_agent_edit(
+            path,
+            "agent-a",
+            baseline_version,
+            vec![Op::Retain(12), Op::Delete(10), Op::Retain(3)],
+        )
+        .await
+        .expect("agent A's delete should apply");
+
+    tracker
+        .apply_agent_edit(
+            path,
+            "agent-b",
+            baseline_version,
+            vec![Op::Retain(12), Op::Insert("/* note */ ".to_string()), Op::Retain(13)],
+        )
+        .await
+        .expect("agent B's insert should apply even though A deleted nearby text");
+
+    assert!(
+        tracker.conflicts_for(path).await.is_empty(),
+        "an insert adjacent to a concurrent delete should not be reported as a conflict"
+    );
+}
+
+/// Test fleet monitor: a snapshot with no agents running renders a valid,
+/// scrapeable Prometheus-style metrics document.
+#[tokio::test]
+async fn test_fleet_monitor_renders_metrics_with_no_agents() {
+    use codex_mcp_server::fleet_monitor::FleetMonitor;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let runtime = create_test_runtime(temp_dir.path().to_path_buf())
+        .await
+        .expect("Failed to create runtime");
+    let integration = AsyncSubAgentIntegration::new(runtime);
+
+    let monitor = FleetMonitor::new(TEST_RUNTIME_BUDGET);
+    monitor.snapshot_once(&integration).await;
+    let rendered = monitor.render_metrics().await;
+
+    assert!(
+        rendered.contains("codex_subagent_active_agents 0"),
+        "should report zero active agents: {rendered}"
+    );
+    assert!(
+        rendered.contains("codex_subagent_stalled_agents 0"),
+        "should report zero stalled agents: {rendered}"
+    );
+}
+
+/// Test subagent tool: get_errors action when error tracking is not wired up
+#[tokio::test]
+async fn test_subagent_get_errors_not_configured() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let runtime = create_test_runtime(temp_dir.path().to_path_buf())
+        .await
+        .expect("Failed to create runtime");
+
+    let integration = Arc::new(AsyncSubAgentIntegration::new(runtime));
+
+    let arguments = serde_json::json!({
+        "action": "get_errors"
+    });
+
+    let result = codex_mcp_server::subagent_tool_handler::handle_subagent_tool_call(
+        arguments,
+        &Some(integration),
+        &None,
+        &None,
+        &None,
+        &None,
+        &[],
+        &None,
+        &None,
+    )
+    .await
+    .expect("get_errors should succeed even without a configured error log");
+
+    if let ContentBlock::TextContent(text) = &result.content[0] {
+        assert!(
+            text.text.contains("not configured"),
+            "Should say error tracking is not configured"
+        );
+    } else {
+        panic!("Expected TextContent");
+    }
+}
+
+/// Test remote runner pool: work acquired by a runner is handed out exactly
+/// once, and re-queued if the runner drops its connection mid-task.
+#[tokio::test]
+async fn test_runner_pool_requeues_on_early_eof() {
+    use codex_mcp_server::subagent_runner::RunnerPool;
+    use codex_mcp_server::subagent_runner::TaskDescriptor;
+    use std::time::Duration;
+
+    let pool = Arc::new(RunnerPool::new(Duration::from_secs(60)));
+    pool.enqueue(TaskDescriptor {
+        agent_id: "agent-1".to_string(),
+        agent_type: AgentType::CodeExpert,
+        task: "Review module".to_string(),
+        budget: None,
+        conversation_id: ConversationId::default(),
+    })
+    .await;
+
+    assert_eq!(pool.pending_count().await, 1);
+
+    let acquired = pool.acquire_work("runner-a").await;
+    assert_eq!(acquired.agent_id, "agent-1");
+    assert_eq!(pool.pending_count().await, 0);
+
+    pool.heartbeat("agent-1")
+        .await
+        .expect("heartbeat should succeed for in-flight agent");
+
+    pool.requeue_on_early_eof("agent-1")
+        .await
+        .expect("should re-queue on early EOF");
+    assert_eq!(
+        pool.pending_count().await,
+        1,
+        "task should be back in the queue after the runner dropped"
+    );
+}
+
+/// Test subagent tool: `start_task` dispatches to the remote runner pool
+/// instead of running in-process once one is configured, and a runner can
+/// then pull the queued task via `acquire_work`.
+#[tokio::test]
+async fn test_start_task_dispatches_to_runner_pool() {
+    use codex_mcp_server::subagent_runner::RunnerPool;
+    use std::time::Duration;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let runtime = create_test_runtime(temp_dir.path().to_path_buf())
+        .await
+        .expect("Failed to create runtime");
+    let integration = Arc::new(AsyncSubAgentIntegration::new(runtime));
+    let pool = Arc::new(RunnerPool::new(Duration::from_secs(60)));
+
+    let arguments = serde_json::json!({
+        "action": "start_task",
+        "agent_type": "CodeExpert",
+        "task": "Review the diff"
+    });
+
+    let result = codex_mcp_server::subagent_tool_handler::handle_subagent_tool_call(
+        arguments,
+        &Some(integration),
+        &Some(pool.clone()),
+        &None,
+        &None,
+        &None,
+        &[],
+        &None,
+        &None,
+    )
+    .await
+    .expect("start_task should succeed with a runner pool configured");
+
+    if let ContentBlock::TextContent(text) = &result.content[0] {
+        assert!(
+            text.text.contains("queued for a remote runner"),
+            "start_task should report the task was queued: {}",
+            text.text
+        );
+    } else {
+        panic!("Expected TextContent");
+    }
+
+    assert_eq!(
+        pool.pending_count().await,
+        1,
+        "start_task should have enqueued exactly one task onto the runner pool"
+    );
+
+    let descriptor = pool.acquire_work("runner-a").await;
+    assert_eq!(descriptor.task, "Review the diff");
+    assert_eq!(descriptor.agent_type, AgentType::CodeExpert);
+}
+
+/// Test subagent tool: `register_file_baseline` and `submit_file_edit`
+/// actually reach the `FileVersionTracker` instead of only its own test
+/// calling `set_baseline`/`apply_agent_edit` directly.
+#[tokio::test]
+async fn test_submit_file_edit_reaches_file_version_tracker() {
+    use codex_mcp_server::ot_merge::FileVersionTracker;
+    use std::path::Path;
+
+    if env::var(OPENAI_API_KEY_ENV_VAR).is_err() && env::var(CODEX_API_KEY_ENV_VAR).is_err() {
+        println!("Skipping test: No API key available");
+        return;
+    }
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let runtime = create_test_runtime(temp_dir.path().to_path_buf())
+        .await
+        .expect("Failed to create runtime");
+    let integration = Arc::new(AsyncSubAgentIntegration::new(runtime));
+    let file_versions = Arc::new(FileVersionTracker::new());
+
+    let baseline_args = serde_json::json!({
+        "action": "register_file_baseline",
+        "path": "shared.rs",
+        "content": "fn main() {}"
+    });
+    codex_mcp_server::subagent_tool_handler::handle_subagent_tool_call(
+        baseline_args,
+        &Some(integration.clone()),
+        &None,
+        &None,
+        &None,
+        &None,
+        &[],
+        &Some(file_versions.clone()),
+        &None,
+    )
+    .await
+    .expect("register_file_baseline should succeed");
+
+    let edit_args = serde_json::json!({
+        "action": "submit_file_edit",
+        "agent_id": "agent-a",
+        "path": "shared.rs",
+        "baseline_version": 1,
+        "ops": [{"insert": "// start\n"}, {"retain": 12}]
+    });
+    let result = codex_mcp_server::subagent_tool_handler::handle_subagent_tool_call(
+        edit_args,
+        &Some(integration),
+        &None,
+        &None,
+        &None,
+        &None,
+        &[],
+        &Some(file_versions.clone()),
+        &None,
+    )
+    .await
+    .expect("submit_file_edit should succeed");
+
+    if let ContentBlock::TextContent(text) = &result.content[0] {
+        assert!(
+            text.text.contains("Edit Applied"),
+            "should report the edit was applied: {}",
+            text.text
+        );
+    } else {
+        panic!("Expected TextContent");
+    }
+
+    assert!(
+        file_versions.conflicts_for(Path::new("shared.rs")).await.is_empty(),
+        "a single agent's edit against its own baseline should never conflict"
+    );
+}